@@ -0,0 +1,249 @@
+//! Async analogues of `OpenatDirExt`/`FileWriter`, enabled by the `async` feature.
+//!
+//! Everything here is a thin bridge: the actual `openat` calls are still
+//! blocking syscalls, so each one is dispatched onto Tokio's blocking thread
+//! pool via `spawn_blocking` and `.await`ed.  The atomic-replace flow (the
+//! temp-file `link_file_at` + `local_rename` dance) and the drop-bomb
+//! "must complete or abandon" invariant are unchanged from `FileWriter`;
+//! only the I/O itself moves off the async executor's reactor thread.
+
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::{fs, io};
+use tokio::io::AsyncWriteExt;
+use tokio::task;
+
+/// Async analogue of [`FileWriter`](crate::FileWriter).  As with `FileWriter`,
+/// you must explicitly invoke either `complete()`/`complete_with()` or
+/// `abandon()`; letting this value drop without doing so will panic.
+pub struct AsyncFileWriter {
+    /// Write to the destination file.
+    pub writer: tokio::io::BufWriter<tokio::fs::File>,
+    /// This string will be used as a prefix for the temporary file
+    pub tmp_prefix: String,
+    /// This string will be used as a suffix for the temporary file
+    pub tmp_suffix: String,
+
+    destname: PathBuf,
+    dir: Arc<openat::Dir>,
+    bomb: drop_bomb::DropBomb,
+}
+
+impl AsyncFileWriter {
+    fn new(dir: Arc<openat::Dir>, f: fs::File, destname: PathBuf) -> Self {
+        Self {
+            writer: tokio::io::BufWriter::new(tokio::fs::File::from_std(f)),
+            tmp_prefix: ".tmp.".to_string(),
+            tmp_suffix: ".tmp".to_string(),
+            destname,
+            dir,
+            bomb: drop_bomb::DropBomb::new(
+                "AsyncFileWriter must be explicitly completed/abandoned to ensure errors are checked",
+            ),
+        }
+    }
+
+    /// Flush any outstanding buffered data and rename the temporary file into
+    /// place.  The provided closure is invoked on the real underlying file
+    /// descriptor, on the blocking thread pool, before it is renamed into
+    /// place; use it to change file attributes such as the mode or owner.
+    pub async fn complete_with<F>(mut self, f: F) -> io::Result<()>
+    where
+        F: FnOnce(&fs::File) -> io::Result<()> + Send + 'static,
+    {
+        self.bomb.defuse();
+        self.writer.flush().await?;
+        let fd = self.writer.into_inner().into_std().await;
+        let dir = self.dir;
+        let destname = self.destname;
+        let tmp_prefix = self.tmp_prefix;
+        let tmp_suffix = self.tmp_suffix;
+        task::spawn_blocking(move || {
+            f(&fd)?;
+            let mut rng = rand::thread_rng();
+            let tmpname = loop {
+                let mut tmpname = tmp_prefix.clone();
+                for _ in 0..8 {
+                    tmpname.push(rand::Rng::sample(&mut rng, rand::distributions::Alphanumeric))
+                }
+                tmpname.push_str(tmp_suffix.as_str());
+                match dir.link_file_at(&fd, tmpname.as_str()) {
+                    Ok(()) => break tmpname,
+                    Err(e) => {
+                        if e.kind() == io::ErrorKind::AlreadyExists {
+                            continue;
+                        } else {
+                            return Err(e);
+                        }
+                    }
+                }
+            };
+            let tmpname = tmpname.as_str();
+            match dir.local_rename(tmpname, &destname) {
+                Ok(()) => Ok(()),
+                Err(e) => {
+                    let _ = dir.remove_file(tmpname);
+                    Err(e)
+                }
+            }
+        })
+        .await
+        .expect("blocking task panicked")
+    }
+
+    /// Flush any outstanding buffered data and rename the temporary file into
+    /// place.
+    pub async fn complete(self) -> io::Result<()> {
+        self.complete_with(|_f| Ok(())).await
+    }
+
+    /// Drop any buffered data and delete the temporary file without
+    /// affecting the final destination.
+    pub fn abandon(mut self) {
+        self.bomb.defuse()
+    }
+}
+
+/// Async analogues of [`OpenatDirExt`](crate::OpenatDirExt), for use from a
+/// Tokio runtime.  Implemented on `openat::Dir`, but every method takes
+/// `self: &Arc<Self>` (so it is called on an `Arc<openat::Dir>`) because the
+/// blocking calls are dispatched onto `spawn_blocking`, which requires owned,
+/// `'static` data.
+#[allow(async_fn_in_trait)]
+pub trait AsyncOpenatDirExt {
+    /// Async analogue of `OpenatDirExt::new_file_writer`.
+    async fn new_async_file_writer<P: AsRef<Path> + Send + 'static>(
+        self: &Arc<Self>,
+        destname: P,
+        mode: libc::mode_t,
+    ) -> io::Result<AsyncFileWriter>;
+
+    /// Async analogue of `OpenatDirExt::write_file_with`.  `f` takes the
+    /// writer by `&mut` reference and returns a boxed future borrowing it,
+    /// rather than a bare associated-type future, so that implementations
+    /// which actually `.await` while holding the `&mut` borrow (the whole
+    /// point of this being async) type-check: a plain `FnOnce(&mut W) ->
+    /// Fut` has no lifetime relating the borrow to `Fut`, which is too
+    /// restrictive for any closure that awaits on the writer.
+    async fn write_file_with_async<P, F, T, E>(
+        self: &Arc<Self>,
+        destname: P,
+        mode: libc::mode_t,
+        f: F,
+    ) -> Result<T, E>
+    where
+        P: AsRef<Path> + Send + 'static,
+        F: for<'r> FnOnce(
+            &'r mut tokio::io::BufWriter<tokio::fs::File>,
+        ) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'r>>,
+        E: From<io::Error>;
+
+    /// Async analogue of `OpenatDirExt::write_file_contents`.
+    async fn write_file_contents_async<P, C>(
+        self: &Arc<Self>,
+        destname: P,
+        mode: libc::mode_t,
+        contents: C,
+    ) -> io::Result<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+        C: AsRef<[u8]> + Send + 'static;
+}
+
+impl AsyncOpenatDirExt for openat::Dir {
+    async fn new_async_file_writer<P: AsRef<Path> + Send + 'static>(
+        self: &Arc<Self>,
+        destname: P,
+        mode: libc::mode_t,
+    ) -> io::Result<AsyncFileWriter> {
+        let dir = Arc::clone(self);
+        let blocking_dir = Arc::clone(&dir);
+        let tmpf = task::spawn_blocking(move || blocking_dir.new_unnamed_file(mode))
+            .await
+            .expect("blocking task panicked")?;
+        Ok(AsyncFileWriter::new(
+            dir,
+            tmpf,
+            destname.as_ref().to_path_buf(),
+        ))
+    }
+
+    async fn write_file_with_async<P, F, T, E>(
+        self: &Arc<Self>,
+        destname: P,
+        mode: libc::mode_t,
+        f: F,
+    ) -> Result<T, E>
+    where
+        P: AsRef<Path> + Send + 'static,
+        F: for<'r> FnOnce(
+            &'r mut tokio::io::BufWriter<tokio::fs::File>,
+        ) -> Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'r>>,
+        E: From<io::Error>,
+    {
+        let mut w = self.new_async_file_writer(destname, mode).await?;
+        match f(&mut w.writer).await {
+            Ok(v) => {
+                w.complete().await?;
+                Ok(v)
+            }
+            Err(e) => {
+                w.abandon();
+                Err(e)
+            }
+        }
+    }
+
+    async fn write_file_contents_async<P, C>(
+        self: &Arc<Self>,
+        destname: P,
+        mode: libc::mode_t,
+        contents: C,
+    ) -> io::Result<()>
+    where
+        P: AsRef<Path> + Send + 'static,
+        C: AsRef<[u8]> + Send + 'static,
+    {
+        self.write_file_with_async(destname, mode, move |w| {
+            Box::pin(async move { w.write_all(contents.as_ref()).await })
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{error, result};
+    use tempfile;
+
+    type Result<T> = result::Result<T, Box<dyn error::Error>>;
+
+    #[tokio::test]
+    async fn write_file_contents_async() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        let d = Arc::new(openat::Dir::open(td.path())?);
+        d.write_file_contents_async("foo", 0o644, "hello world").await?;
+        assert_eq!(std::fs::read(td.path().join("foo"))?, b"hello world");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_file_with_async_abandon_on_error() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        let d = Arc::new(openat::Dir::open(td.path())?);
+        let r: io::Result<()> = d
+            .write_file_with_async("foo", 0o644, |w| {
+                Box::pin(async move {
+                    w.write_all(b"partial").await?;
+                    Err(io::Error::new(io::ErrorKind::Other, "boom"))
+                })
+            })
+            .await;
+        assert!(r.is_err());
+        assert!(!td.path().join("foo").exists());
+        Ok(())
+    }
+}
@@ -11,12 +11,22 @@
 //! More recently, there is also an `FileExt` available; it currently
 //! just contains an optimized file copy method that will hopefully
 //! go into the standard library.
+//!
+//! Enabling the `async` feature adds [`AsyncFileWriter`] and
+//! [`AsyncOpenatDirExt`], which mirror the atomic-replace flow of `FileWriter` but run
+//! their blocking `openat` calls on Tokio's blocking thread pool so they can
+//! be `.await`ed without stalling the calling task.
 
 #![deny(unused_results)]
 #![deny(missing_docs)]
 // We're just a wrapper around openat, shouldn't have any unsafe here.
 #![forbid(unsafe_code)]
 
+#[cfg(feature = "async")]
+mod asynch;
+#[cfg(feature = "async")]
+pub use asynch::{AsyncFileWriter, AsyncOpenatDirExt};
+
 use libc;
 use nix;
 use openat;
@@ -25,6 +35,7 @@ use std::ffi::OsStr;
 use std::fs::File;
 use std::io::prelude::*;
 use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
 use std::os::unix::prelude::FileExt as UnixFileExt;
 use std::path::{Path, PathBuf};
 use std::{fs, io};
@@ -40,12 +51,30 @@ pub trait OpenatDirExt {
     /// Remove a file from the given directory.
     fn remove_file_optional<P: openat::AsPath>(&self, p: P) -> io::Result<()>;
 
+    /// Recursively remove a directory and everything beneath it, the same
+    /// way `std::fs::remove_dir_all` does but relative to this `Dir` and
+    /// without following symlinks into other directories.
+    fn remove_dir_all<P: openat::AsPath>(&self, p: P) -> io::Result<()>;
+
+    /// Like `remove_dir_all()` except returns `Ok(())` for a nonexistent path.
+    fn remove_dir_all_optional<P: openat::AsPath>(&self, p: P) -> io::Result<()>;
+
     /// Like `open_file_optional()` except opens a directory via `openat::dir::sub_dir`.
     fn sub_dir_optional<P: openat::AsPath>(&self, p: P) -> io::Result<Option<openat::Dir>>;
 
     /// Like `metadata()` except returns `Ok(None)` for nonexistent paths.
     fn metadata_optional<P: openat::AsPath>(&self, p: P) -> io::Result<Option<openat::Metadata>>;
 
+    /// `lstat()`-equivalent of `metadata_optional()`: the path itself is not
+    /// dereferenced.  `openat::Dir::metadata()` already passes
+    /// `AT_SYMLINK_NOFOLLOW` internally, so this is simply an alias for
+    /// `metadata_optional()`, kept as a separate name for callers who want
+    /// to be explicit that they rely on symlinks not being followed.
+    fn symlink_metadata_optional<P: openat::AsPath>(
+        &self,
+        p: P,
+    ) -> io::Result<Option<openat::Metadata>>;
+
     /// On modern filesystems the directory entry contains the type; if available,
     /// return it.  Otherwise invoke `stat()`.
     fn get_file_type(&self, e: &openat::Entry) -> io::Result<openat::SimpleType>;
@@ -57,6 +86,11 @@ pub trait OpenatDirExt {
     /// Create a directory but don't error if it already exists.
     fn ensure_dir<P: openat::AsPath>(&self, p: P, mode: libc::mode_t) -> io::Result<()>;
 
+    /// Like `ensure_dir()`, but reports whether the directory was freshly
+    /// created (`true`) or already existed (`false`).  Errors if the path
+    /// exists but is not a directory.
+    fn ensure_dir_with<P: openat::AsPath>(&self, p: P, mode: libc::mode_t) -> io::Result<bool>;
+
     /// Create directory and all parents as necessary; no error is returned if directory already exists.
     fn ensure_dir_all<P: openat::AsPath>(&self, p: P, mode: libc::mode_t) -> io::Result<()>;
 
@@ -68,6 +102,19 @@ pub trait OpenatDirExt {
         mode: libc::mode_t,
     ) -> io::Result<FileWriter>;
 
+    /// Create a `SpooledFileWriter`, which buffers written data in memory
+    /// and only creates a backing temp file (as `new_file_writer` does) once
+    /// more than `threshold` bytes have been written.  Either way,
+    /// `complete()` atomically creates/replaces the destination, so this
+    /// gives the same atomicity guarantees as `new_file_writer` without the
+    /// inode churn of an on-disk temp file for small writes.
+    fn new_spooled_file_writer<'a, P: AsRef<Path>>(
+        &'a self,
+        destname: P,
+        mode: libc::mode_t,
+        threshold: usize,
+    ) -> io::Result<SpooledFileWriter<'a>>;
+
     /// Atomically create or replace the destination file, calling the provided
     /// function to generate the contents.  Note that the contents of the
     /// file will not be explicitly sync'd to disk; if you want to do so you
@@ -131,6 +178,19 @@ pub trait OpenatDirExt {
     ) -> io::Result<()> {
         self.write_file_with(destname, mode, |w| w.write_all(contents.as_ref()))
     }
+
+    /// Copy `src` to `destname`, atomically creating the destination via the
+    /// same temporary-file-then-rename path as `new_file_writer`, and
+    /// replicating `src`'s permission bits.  If `copy_times` is true, the
+    /// access and modification times are replicated too.  If `src` is not a
+    /// regular file, its permission bits are only used as the creation mode
+    /// (so the umask applies) and its times are not copied.
+    fn copy_file_at<P: AsRef<Path>>(
+        &self,
+        src: &File,
+        destname: P,
+        copy_times: bool,
+    ) -> io::Result<()>;
 }
 
 impl OpenatDirExt for openat::Dir {
@@ -160,6 +220,30 @@ impl OpenatDirExt for openat::Dir {
         }
     }
 
+    fn remove_dir_all<P: openat::AsPath>(&self, p: P) -> io::Result<()> {
+        let p = p
+            .to_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "nul byte in file name"))?;
+        let p = p.as_ref();
+        let p = Path::new(OsStr::from_bytes(p.to_bytes()));
+        let sub = self.sub_dir(p)?;
+        impl_remove_dir_all_contents(&sub)?;
+        self.remove_dir(p)
+    }
+
+    fn remove_dir_all_optional<P: openat::AsPath>(&self, p: P) -> io::Result<()> {
+        match self.remove_dir_all(p) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                if e.kind() == io::ErrorKind::NotFound {
+                    Ok(())
+                } else {
+                    Err(e)
+                }
+            }
+        }
+    }
+
     fn metadata_optional<P: openat::AsPath>(&self, p: P) -> io::Result<Option<openat::Metadata>> {
         match self.metadata(p) {
             Ok(d) => Ok(Some(d)),
@@ -173,6 +257,13 @@ impl OpenatDirExt for openat::Dir {
         }
     }
 
+    fn symlink_metadata_optional<P: openat::AsPath>(
+        &self,
+        p: P,
+    ) -> io::Result<Option<openat::Metadata>> {
+        self.metadata_optional(p)
+    }
+
     fn sub_dir_optional<P: openat::AsPath>(&self, p: P) -> io::Result<Option<openat::Dir>> {
         match self.sub_dir(p) {
             Ok(d) => Ok(Some(d)),
@@ -208,11 +299,26 @@ impl OpenatDirExt for openat::Dir {
     }
 
     fn ensure_dir<P: openat::AsPath>(&self, p: P, mode: libc::mode_t) -> io::Result<()> {
+        self.ensure_dir_with(p, mode).map(|_created| ())
+    }
+
+    fn ensure_dir_with<P: openat::AsPath>(&self, p: P, mode: libc::mode_t) -> io::Result<bool> {
+        let p = p
+            .to_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "nul byte in file name"))?;
+        let p = p.as_ref();
+        let p = Path::new(OsStr::from_bytes(p.to_bytes()));
         match self.create_dir(p, mode) {
-            Ok(_) => Ok(()),
+            Ok(_) => Ok(true),
             Err(e) => {
                 if e.kind() == io::ErrorKind::AlreadyExists {
-                    Ok(())
+                    if self.metadata(p)?.simple_type() != openat::SimpleType::Dir {
+                        return Err(io::Error::new(
+                            io::ErrorKind::AlreadyExists,
+                            "path exists and is not a directory",
+                        ));
+                    }
+                    Ok(false)
                 } else {
                     Err(e)
                 }
@@ -250,6 +356,59 @@ impl OpenatDirExt for openat::Dir {
         let destname = destname.as_ref();
         Ok(FileWriter::new(self, tmpf, destname.to_path_buf()))
     }
+
+    fn new_spooled_file_writer<'a, P: AsRef<Path>>(
+        &'a self,
+        destname: P,
+        mode: libc::mode_t,
+        threshold: usize,
+    ) -> io::Result<SpooledFileWriter<'a>> {
+        Ok(SpooledFileWriter::new(
+            self,
+            destname.as_ref().to_path_buf(),
+            mode,
+            threshold,
+        ))
+    }
+
+    fn copy_file_at<P: AsRef<Path>>(
+        &self,
+        src: &File,
+        destname: P,
+        copy_times: bool,
+    ) -> io::Result<()> {
+        let meta = src.metadata()?;
+        let is_regular = meta.file_type().is_file();
+        let mode = meta.permissions().mode() & 0o7777;
+        let w = self.new_file_writer(destname, mode)?;
+        if let Err(e) = src.copy_to(w.writer.get_ref()) {
+            w.abandon();
+            return Err(e);
+        }
+        w.complete_with(|f| {
+            if !is_regular {
+                return Ok(());
+            }
+            use std::os::unix::fs::MetadataExt;
+            use std::os::unix::io::AsRawFd;
+            let fd = f.as_raw_fd();
+            nix::sys::stat::fchmod(fd, nix::sys::stat::Mode::from_bits_truncate(mode))
+                .map_err(|e| io::Error::from_raw_os_error(e as i32))?;
+            if copy_times {
+                let atime = nix::sys::time::TimeSpec::from(libc::timespec {
+                    tv_sec: meta.atime(),
+                    tv_nsec: meta.atime_nsec(),
+                });
+                let mtime = nix::sys::time::TimeSpec::from(libc::timespec {
+                    tv_sec: meta.mtime(),
+                    tv_nsec: meta.mtime_nsec(),
+                });
+                nix::sys::stat::futimens(fd, &atime, &mtime)
+                    .map_err(|e| io::Error::from_raw_os_error(e as i32))?;
+            }
+            Ok(())
+        })
+    }
 }
 
 /// Walk up the path components, creating each directory in turn.  This is
@@ -265,6 +424,26 @@ pub(crate) fn impl_ensure_dir_all(d: &openat::Dir, p: &Path, mode: libc::mode_t)
     Ok(())
 }
 
+/// Remove everything inside `sub`, recursing into child directories.
+/// Directory entries are unlinked using the dirent's own file type where
+/// the filesystem provides it, so a symlink (even one pointing at a
+/// directory) is always unlinked rather than followed.
+pub(crate) fn impl_remove_dir_all_contents(sub: &openat::Dir) -> io::Result<()> {
+    for entry in sub.list_dir(".")? {
+        let entry = entry?;
+        let name = entry.file_name();
+        match sub.get_file_type(&entry)? {
+            openat::SimpleType::Dir => {
+                let child = sub.sub_dir(name)?;
+                impl_remove_dir_all_contents(&child)?;
+                sub.remove_dir(name)?;
+            }
+            _ => sub.remove_file(name)?,
+        }
+    }
+    Ok(())
+}
+
 /// A wrapper for atomically replacing a file.  The primary field
 /// to access here is the `writer`.  You can also configure the
 /// temporary prefix and suffix used for the temporary file before
@@ -365,10 +544,192 @@ impl<'a> FileWriter<'a> {
     }
 }
 
+enum SpooledState<'a> {
+    /// Written data so far, not yet backed by a real temporary file.
+    Buffered(Vec<u8>),
+    /// The buffer exceeded its threshold and was spilled to a real
+    /// `FileWriter`, which all further writes go straight to.
+    Spilled(FileWriter<'a>),
+}
+
+/// Like `FileWriter`, but avoids creating a backing temp file (and thus an
+/// inode) until more than some threshold of bytes have been written.  This
+/// is useful when writing many small files, where `new_file_writer`'s
+/// immediate `new_unnamed_file()` call would otherwise generate inode churn
+/// for no benefit.
+///
+/// As with `FileWriter`, you must explicitly invoke either
+/// `complete()`/`complete_with()` or `abandon()`; letting this value drop
+/// without doing so will panic.
+pub struct SpooledFileWriter<'a> {
+    dir: &'a openat::Dir,
+    destname: PathBuf,
+    mode: libc::mode_t,
+    threshold: usize,
+    state: SpooledState<'a>,
+    bomb: drop_bomb::DropBomb,
+}
+
+impl<'a> SpooledFileWriter<'a> {
+    fn new(dir: &'a openat::Dir, destname: PathBuf, mode: libc::mode_t, threshold: usize) -> Self {
+        Self {
+            dir,
+            destname,
+            mode,
+            threshold,
+            state: SpooledState::Buffered(Vec::new()),
+            bomb: drop_bomb::DropBomb::new(
+                "SpooledFileWriter must be explicitly completed/abandoned to ensure errors are checked",
+            ),
+        }
+    }
+
+    /// Once the in-memory buffer exceeds `threshold`, spill it to a real
+    /// temporary file so that further writes (and the eventual `complete()`)
+    /// go through the same path as `FileWriter`.
+    fn spill(&mut self) -> io::Result<()> {
+        if let SpooledState::Buffered(buf) = &self.state {
+            let mut fw = self.dir.new_file_writer(&self.destname, self.mode)?;
+            if let Err(e) = fw.writer.write_all(buf) {
+                fw.abandon();
+                return Err(e);
+            }
+            self.state = SpooledState::Spilled(fw);
+        }
+        Ok(())
+    }
+
+    /// Flush any outstanding buffered data and rename the temporary file
+    /// into place, as `FileWriter::complete_with` does.  If the data never
+    /// spilled to a real temp file, one is created now so the on-disk
+    /// result is identical either way.
+    pub fn complete_with<F>(mut self, f: F) -> io::Result<()>
+    where
+        F: Fn(&fs::File) -> io::Result<()>,
+    {
+        self.bomb.defuse();
+        match self.state {
+            SpooledState::Spilled(fw) => fw.complete_with(f),
+            SpooledState::Buffered(buf) => {
+                let mut fw = self.dir.new_file_writer(&self.destname, self.mode)?;
+                if let Err(e) = fw.writer.write_all(&buf) {
+                    fw.abandon();
+                    return Err(e);
+                }
+                fw.complete_with(f)
+            }
+        }
+    }
+
+    /// Flush any outstanding buffered data and rename the temporary file
+    /// into place.
+    pub fn complete(self) -> io::Result<()> {
+        self.complete_with(|_f| Ok(()))
+    }
+
+    /// Drop any buffered data and delete the temporary file (if one was
+    /// created) without affecting the final destination.
+    pub fn abandon(mut self) {
+        self.bomb.defuse();
+        if let SpooledState::Spilled(fw) = self.state {
+            fw.abandon();
+        }
+    }
+}
+
+impl<'a> Write for SpooledFileWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.state {
+            SpooledState::Buffered(v) => {
+                v.extend_from_slice(buf);
+                if v.len() > self.threshold {
+                    self.spill()?;
+                }
+            }
+            SpooledState::Spilled(fw) => {
+                fw.writer.write_all(buf)?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.state {
+            SpooledState::Buffered(_) => Ok(()),
+            SpooledState::Spilled(fw) => fw.writer.flush(),
+        }
+    }
+}
+
 // Our methods take &self, not &mut self matching the other raw
 // file methods.  We can't use io::copy because it expects mutable
 // references, so just reimplement it here.
+//
+// On Linux/Android we first try a hole-aware copy via `SEEK_DATA`/`SEEK_HOLE`
+// so that copying a sparse file doesn't materialize its holes; if the
+// filesystem doesn't support that (e.g. `EINVAL`) we fall back to copying
+// every byte.
 pub(crate) fn fallback_file_copy(src: &File, dest: &File) -> io::Result<u64> {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let len = src.metadata()?.len();
+        if sparse_file_copy(src, dest, len)? {
+            return Ok(len);
+        }
+    }
+    byte_by_byte_file_copy(src, dest)
+}
+
+/// Copy `src` to `dest` by iterating only over `src`'s allocated data
+/// segments (via `lseek(SEEK_DATA)`/`lseek(SEEK_HOLE)`), then truncating
+/// `dest` to `len` so trailing holes are preserved.  Returns `Ok(false)`
+/// (without having written anything) if the filesystem doesn't support
+/// `SEEK_DATA`, so the caller can fall back to `byte_by_byte_file_copy`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn sparse_file_copy(src: &File, dest: &File, len: u64) -> io::Result<bool> {
+    use nix::errno::Errno;
+    use nix::unistd::{lseek, Whence};
+    use std::os::unix::io::AsRawFd;
+
+    let srcfd = src.as_raw_fd();
+    let mut buf = [0u8; 128 * 1024];
+    let mut off: u64 = 0;
+    loop {
+        let data_start = match lseek(srcfd, off as i64, Whence::SeekData) {
+            Ok(o) => o as u64,
+            // No more data after `off`; the remainder of the file is a hole
+            // (this is also what we get for an entirely sparse file).
+            Err(Errno::ENXIO) => {
+                dest.set_len(len)?;
+                return Ok(true);
+            }
+            // This filesystem doesn't implement SEEK_DATA.
+            Err(Errno::EINVAL) => return Ok(false),
+            Err(e) => return Err(io::Error::from_raw_os_error(e as i32)),
+        };
+        let hole_start = match lseek(srcfd, data_start as i64, Whence::SeekHole) {
+            Ok(o) => o as u64,
+            Err(e) => return Err(io::Error::from_raw_os_error(e as i32)),
+        };
+        let mut pos = data_start;
+        while pos < hole_start {
+            let want = std::cmp::min(buf.len() as u64, hole_start - pos) as usize;
+            let n = src.read_at(&mut buf[..want], pos)?;
+            if n == 0 {
+                break;
+            }
+            dest.write_all_at(&buf[..n], pos)?;
+            pos += n as u64;
+        }
+        if hole_start >= len {
+            dest.set_len(len)?;
+            return Ok(true);
+        }
+        off = hole_start;
+    }
+}
+
+fn byte_by_byte_file_copy(src: &File, dest: &File) -> io::Result<u64> {
     let mut off: u64 = 0;
     let mut buf = [0u8; 8192];
     loop {
@@ -497,6 +858,21 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn symlink_metadata_optional() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        let d = openat::Dir::open(td.path())?;
+        assert!(d.symlink_metadata_optional("link")?.is_none());
+        std::os::unix::fs::symlink("nonexistent-target", td.path().join("link"))?;
+        // A dangling symlink is reported as present: `openat::Dir::metadata()`
+        // already passes `AT_SYMLINK_NOFOLLOW` internally, so `metadata_optional()`
+        // agrees with `symlink_metadata_optional()` here.
+        assert!(d.metadata_optional("link")?.is_some());
+        let m = d.symlink_metadata_optional("link")?.unwrap();
+        assert_eq!(m.simple_type(), openat::SimpleType::Symlink);
+        Ok(())
+    }
+
     #[test]
     fn get_file_type() -> Result<()> {
         let td = tempfile::tempdir()?;
@@ -527,6 +903,53 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn ensure_dir_with() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        let d = openat::Dir::open(td.path())?;
+        assert!(d.ensure_dir_with("foo", 0o755)?);
+        assert!(!d.ensure_dir_with("foo", 0o755)?);
+        d.write_file("bar", 0o644)?.sync_all()?;
+        assert!(d.ensure_dir_with("bar", 0o755).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn spooled_file_writer_unspilled() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        let d = openat::Dir::open(td.path())?;
+        let mut w = d.new_spooled_file_writer("foo", 0o644, 4096)?;
+        w.write_all(b"small")?;
+        w.complete()?;
+        assert_eq!(d.open_file("foo")?.metadata()?.len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn spooled_file_writer_spilled() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        let d = openat::Dir::open(td.path())?;
+        let mut w = d.new_spooled_file_writer("foo", 0o644, 4)?;
+        w.write_all(b"more than four bytes")?;
+        w.complete()?;
+        assert_eq!(d.open_file("foo")?.metadata()?.len(), 20);
+        Ok(())
+    }
+
+    #[test]
+    fn remove_dir_all() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        let d = openat::Dir::open(td.path())?;
+        d.ensure_dir_all("a/b", 0o755)?;
+        d.write_file("a/b/c", 0o644)?.sync_all()?;
+        d.write_file("a/top", 0o644)?.sync_all()?;
+        assert!(d.exists("a")?);
+        d.remove_dir_all("a")?;
+        assert!(!d.exists("a")?);
+        d.remove_dir_all_optional("a")?;
+        Ok(())
+    }
+
     fn find_test_file(tempdir: &Path) -> Result<PathBuf> {
         for p in ["/proc/self/exe", "/usr/bin/bash"].iter() {
             let p = Path::new(p);
@@ -539,6 +962,46 @@ mod tests {
         Ok(fallback)
     }
 
+    #[test]
+    fn copy_fallback_sparse() -> Result<()> {
+        use std::io::Read;
+        use std::os::unix::fs::MetadataExt;
+
+        let td = tempfile::tempdir()?;
+        let src_p = td.path().join("sparse-src");
+        let dest_p = td.path().join("sparse-dest");
+        let total_len: u64 = 10 * 1024 * 1024 + 3;
+        {
+            let src = File::create(&src_p)?;
+            src.write_all_at(b"start", 0)?;
+            src.write_all_at(b"end", total_len - 3)?;
+            src.set_len(total_len)?;
+        }
+        let copied = {
+            let src = File::open(&src_p)?;
+            let dest = File::create(&dest_p)?;
+            fallback_file_copy(&src, &dest)?
+        };
+        assert_eq!(copied, total_len);
+
+        let mut srcbuf = Vec::new();
+        let _ = File::open(&src_p)?.read_to_end(&mut srcbuf)?;
+        let mut destbuf = Vec::new();
+        let _ = File::open(&dest_p)?.read_to_end(&mut destbuf)?;
+        assert_eq!(srcbuf, destbuf);
+
+        // A fully-materialized copy of a 10MiB+ file would use on the order
+        // of 20,000 512-byte blocks; a hole-preserving copy should use only
+        // a handful, confirming the middle hole wasn't written out.
+        let dest_blocks = std::fs::metadata(&dest_p)?.blocks();
+        assert!(
+            dest_blocks < 256,
+            "expected dest to stay sparse, used {} blocks",
+            dest_blocks
+        );
+        Ok(())
+    }
+
     #[test]
     fn copy_fallback() -> Result<()> {
         use std::io::Read;
@@ -560,4 +1023,48 @@ mod tests {
         assert_eq!(&srcbuf, &destbuf);
         Ok(())
     }
+
+    #[test]
+    fn copy_file_at() -> Result<()> {
+        use std::io::Read;
+        let td = tempfile::tempdir()?;
+        let d = openat::Dir::open(td.path())?;
+        let src_p = td.path().join("src");
+        std::fs::write(&src_p, "some test data")?;
+        let mut perms = std::fs::metadata(&src_p)?.permissions();
+        perms.set_mode(0o600);
+        std::fs::set_permissions(&src_p, perms)?;
+        let src = File::open(&src_p)?;
+        d.copy_file_at(&src, "dest", false)?;
+        let dest_meta = d.metadata("dest")?;
+        assert_eq!(dest_meta.stat().st_mode & 0o7777, 0o600);
+        let mut destbuf = Vec::new();
+        let _ = d.open_file("dest")?.read_to_end(&mut destbuf)?;
+        assert_eq!(destbuf, b"some test data");
+        Ok(())
+    }
+
+    #[test]
+    fn copy_file_at_times() -> Result<()> {
+        use std::os::unix::io::AsRawFd;
+        let td = tempfile::tempdir()?;
+        let d = openat::Dir::open(td.path())?;
+        let src_p = td.path().join("src");
+        std::fs::write(&src_p, "some test data")?;
+        let src = File::open(&src_p)?;
+        let atime = nix::sys::time::TimeSpec::from(libc::timespec {
+            tv_sec: 1_000_000,
+            tv_nsec: 0,
+        });
+        let mtime = nix::sys::time::TimeSpec::from(libc::timespec {
+            tv_sec: 2_000_000,
+            tv_nsec: 0,
+        });
+        nix::sys::stat::futimens(src.as_raw_fd(), &atime, &mtime)?;
+        d.copy_file_at(&src, "dest", true)?;
+        let dest_meta = d.metadata("dest")?;
+        assert_eq!(dest_meta.stat().st_atime, 1_000_000);
+        assert_eq!(dest_meta.stat().st_mtime, 2_000_000);
+        Ok(())
+    }
 }